@@ -0,0 +1,61 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+
+use crate::bytes_buf::BytesBuf;
+use crate::resp::{parse_message, Value};
+
+// Runs `count` SET/GET pairs of `payload_size` bytes each through
+// the real encode/parse path (`Value::encode` and `parse_message`) and
+// prints the resulting throughput, so maintainers can measure the protocol
+// hot path without spinning up a client and server. Each command is encoded
+// and parsed back on its own in a fresh buffer; commands are not pipelined
+// into one shared buffer.
+pub fn run(count: usize, payload_size: usize) {
+    let payload = "x".repeat(payload_size);
+    let mut total_bytes: usize = 0;
+
+    let start = Instant::now();
+    for i in 0..count {
+        let key = format!("key:{}", i);
+
+        total_bytes += roundtrip(Value::Array(vec![
+            Value::BulkString("SET".to_string()),
+            Value::BulkString(key.clone()),
+            Value::BulkString(payload.clone()),
+        ]));
+
+        total_bytes += roundtrip(Value::Array(vec![
+            Value::BulkString("GET".to_string()),
+            Value::BulkString(key),
+        ]));
+    }
+    let elapsed = start.elapsed();
+
+    let mib = total_bytes as f64 / (1024.0 * 1024.0);
+    println!(
+        "bench: {} SET/GET pairs, {} byte payload, {:.2} MiB encoded+parsed in {:?} ({:.2} MiB/s)",
+        count,
+        payload_size,
+        mib,
+        elapsed,
+        mib / elapsed.as_secs_f64()
+    );
+}
+
+// Encodes `value`, then parses it straight back out of a `BytesBuf`,
+// returning the number of bytes the wire representation occupied.
+fn roundtrip(value: Value) -> usize {
+    let encoded = value.encode(2);
+    let len = encoded.len();
+
+    let mut buf = BytesBuf::new();
+    buf.extend(Bytes::from(encoded.into_bytes()));
+
+    let (_value, consumed) = parse_message(&buf, 0)
+        .expect("bench payload is well-formed")
+        .expect("bench payload is never partial");
+    debug_assert_eq!(consumed, len);
+
+    len
+}
@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+
+use bytes::{Bytes, BytesMut};
+
+// A left-consuming, right-extending byte buffer backed by a chain of `Bytes`
+// chunks. Appending never copies (the chunk is just pushed onto the back),
+// and reading only copies the specific range a caller asks for instead of
+// the whole remaining buffer, so repeatedly peeking into a large buffer
+// doesn't degrade into quadratic copying.
+#[derive(Default)]
+pub struct BytesBuf {
+    chunks: VecDeque<Bytes>,
+    buf_len: usize,
+}
+
+impl BytesBuf {
+    // Creates an empty buffer.
+    pub fn new() -> Self {
+        BytesBuf {
+            chunks: VecDeque::new(),
+            buf_len: 0,
+        }
+    }
+
+    // Appends a chunk of bytes to the right of the buffer.
+    pub fn extend(&mut self, bytes: Bytes) {
+        if bytes.is_empty() {
+            return;
+        }
+        self.buf_len += bytes.len();
+        self.chunks.push_back(bytes);
+    }
+
+    // Returns the number of bytes currently buffered.
+    pub fn len(&self) -> usize {
+        self.buf_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf_len == 0
+    }
+
+    // Removes and returns the first `n` bytes of the buffer, or `None` if
+    // fewer than `n` bytes are currently buffered.
+    pub fn take_exact(&mut self, n: usize) -> Option<Bytes> {
+        let out = self.peek(n)?;
+        self.advance(n);
+        Some(out)
+    }
+
+    // Removes and returns every byte currently buffered.
+    pub fn take_all(&mut self) -> Bytes {
+        self.take_exact(self.buf_len).unwrap_or_else(Bytes::new)
+    }
+
+    // Returns the first `n` bytes of the buffer as a single contiguous
+    // `Bytes`, without consuming them. Copies only when the range spans more
+    // than one chunk.
+    pub fn peek(&self, n: usize) -> Option<Bytes> {
+        self.peek_at(0, n)
+    }
+
+    // Returns `len` bytes starting at absolute offset `start`, without
+    // consuming anything. This is the only place that copies, and it only
+    // ever copies the bytes being asked for.
+    pub(crate) fn peek_at(&self, start: usize, len: usize) -> Option<Bytes> {
+        if start + len > self.buf_len {
+            return None;
+        }
+        if len == 0 {
+            return Some(Bytes::new());
+        }
+
+        let mut offset = start;
+        let mut chunks = self.chunks.iter();
+        let mut chunk = chunks.next()?;
+        while offset >= chunk.len() {
+            offset -= chunk.len();
+            chunk = chunks.next()?;
+        }
+
+        if chunk.len() - offset >= len {
+            return Some(chunk.slice(offset..offset + len));
+        }
+
+        let mut out = BytesMut::with_capacity(len);
+        out.extend_from_slice(&chunk[offset..]);
+        let mut remaining = len - (chunk.len() - offset);
+        for chunk in chunks {
+            if remaining == 0 {
+                break;
+            }
+            let take = remaining.min(chunk.len());
+            out.extend_from_slice(&chunk[..take]);
+            remaining -= take;
+        }
+        Some(out.freeze())
+    }
+
+    // Returns the byte at absolute offset `i`, without consuming anything.
+    pub(crate) fn byte_at(&self, i: usize) -> Option<u8> {
+        self.peek_at(i, 1).map(|b| b[0])
+    }
+
+    // Drops the first `n` bytes of the buffer.
+    fn advance(&mut self, mut n: usize) {
+        self.buf_len -= n;
+        while n > 0 {
+            let front = self.chunks.front_mut().expect("advance past end of buffer");
+            if front.len() <= n {
+                n -= front.len();
+                self.chunks.pop_front();
+            } else {
+                *front = front.slice(n..);
+                n = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extend_then_take_exact() {
+        let mut buf = BytesBuf::new();
+        buf.extend(Bytes::from_static(b"hel"));
+        buf.extend(Bytes::from_static(b"lo"));
+        assert_eq!(buf.len(), 5);
+        assert_eq!(buf.take_exact(3).unwrap(), Bytes::from_static(b"hel"));
+        assert_eq!(buf.len(), 2);
+        assert_eq!(buf.take_all(), Bytes::from_static(b"lo"));
+        assert_eq!(buf.len(), 0);
+    }
+
+    #[test]
+    fn peek_across_chunk_boundary() {
+        let mut buf = BytesBuf::new();
+        buf.extend(Bytes::from_static(b"ab"));
+        buf.extend(Bytes::from_static(b"cde"));
+        assert_eq!(buf.peek(4).unwrap(), Bytes::from_static(b"abcd"));
+        // Peeking does not consume.
+        assert_eq!(buf.len(), 5);
+    }
+
+    #[test]
+    fn take_exact_beyond_len_returns_none() {
+        let mut buf = BytesBuf::new();
+        buf.extend(Bytes::from_static(b"ab"));
+        assert!(buf.take_exact(3).is_none());
+    }
+}
@@ -1,50 +1,198 @@
 use anyhow::Result;
-use resp::Value::{BulkString, Error, Null, SimpleString};
+use bytes::Bytes;
+use futures::StreamExt;
+use resp::{RespConnection, Value::{BulkString, Error, Integer, Map, Null, SimpleString}};
 use std::sync::{Arc, Mutex};
 use store::Store;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpListener;
 
+mod bench;
+mod bytes_buf;
 mod resp;
 mod store;
 
+// The protocol versions a client may request via `HELLO`.
+const SUPPORTED_PROTOCOL_VERSIONS: [u8; 2] = [2, 3];
+
+// Builds the `HELLO` reply describing this server, exercising the map,
+// integer and bulk string encoders.
+fn hello_reply(conn: &RespConnection) -> resp::Value {
+    Map(vec![
+        (BulkString("server".to_string()), BulkString("redis-mini".to_string())),
+        (BulkString("version".to_string()), BulkString("0.1.0".to_string())),
+        (BulkString("proto".to_string()), Integer(conn.protocol_version() as i64)),
+        (BulkString("mode".to_string()), BulkString("standalone".to_string())),
+        (BulkString("role".to_string()), BulkString("master".to_string())),
+    ])
+}
+
+// The transport(s) the server listens on, selected by the `--transport`
+// CLI flag.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TransportMode {
+    Tcp,
+    WebSocket,
+    Both,
+}
+
+// Parses the `--transport=tcp|ws|both` flag from the process arguments.
+// Defaults to `Tcp` if the flag isn't present or isn't recognised.
+fn parse_transport_mode() -> TransportMode {
+    let flag = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--transport=").map(str::to_string));
+
+    match flag.as_deref() {
+        Some("ws") => TransportMode::WebSocket,
+        Some("both") => TransportMode::Both,
+        _ => TransportMode::Tcp,
+    }
+}
+
+const DEFAULT_BENCH_COUNT: usize = 100_000;
+const DEFAULT_BENCH_PAYLOAD_SIZE: usize = 64;
+
+// Parses the `--bench` flag (and its optional `--count=` / `--payload-size=`
+// companions) from the process arguments. Returns `None` unless `--bench`
+// was passed.
+fn parse_bench_args() -> Option<(usize, usize)> {
+    let args: Vec<String> = std::env::args().collect();
+    if !args.iter().any(|arg| arg == "--bench") {
+        return None;
+    }
+
+    let count = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--count="))
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_BENCH_COUNT);
+    let payload_size = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--payload-size="))
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_BENCH_PAYLOAD_SIZE);
+
+    Some((count, payload_size))
+}
+
+// Starts a 100 Hz sampling CPU profiler. Entirely compiled out unless the
+// `profiling` feature is enabled, so release builds pay nothing for it.
+#[cfg(feature = "profiling")]
+fn start_profiler() -> pprof::ProfilerGuard<'static> {
+    pprof::ProfilerGuardBuilder::default()
+        .frequency(100)
+        .build()
+        .expect("failed to start profiler")
+}
+
+// Writes the sampled profile to `profile.pb` in pprof format for flamegraph analysis.
+#[cfg(feature = "profiling")]
+fn write_profile(guard: pprof::ProfilerGuard<'static>) {
+    use pprof::protos::Message;
+    use std::io::Write;
+
+    let report = match guard.report().build() {
+        Ok(report) => report,
+        Err(e) => {
+            println!("failed to build profile report: {}", e);
+            return;
+        }
+    };
+    let profile = report.pprof().expect("failed to build pprof profile");
+
+    let mut bytes = Vec::new();
+    profile.write_to_vec(&mut bytes).expect("failed to serialize profile");
+
+    match std::fs::File::create("profile.pb") {
+        Ok(mut file) => {
+            if let Err(e) = file.write_all(&bytes) {
+                println!("failed to write profile.pb: {}", e);
+            } else {
+                println!("wrote CPU profile to profile.pb");
+            }
+        }
+        Err(e) => println!("failed to create profile.pb: {}", e),
+    }
+}
+
+// Reads a streamed bulk value off `conn` to completion, collecting its
+// chunks into a `Vec<Bytes>`. This runs entirely before the store lock is
+// taken, so the (non-async-aware) `Mutex` guarding `Store` is never held
+// across the `.await` points of the network read.
+async fn collect_streamed_chunks(conn: &mut RespConnection) -> Result<Vec<Bytes>> {
+    let mut chunks = Vec::new();
+    let mut body = conn.read_value_streaming();
+    while let Some(chunk) = body.next().await {
+        chunks.push(chunk?);
+    }
+    Ok(chunks)
+}
 
 /*
-Description: This function receives a TcpStream and an Arc<Mutex<Store>> object as arguments and handles a connection to a key-value store.
+Description: This function receives a RespConnection and an Arc<Mutex<Store>> object as arguments and handles a connection to a key-value store.
 
 Args:
-- stream: TcpStream object that represents the connection to the client
+- conn: RespConnection object that represents the connection to the client, over either raw TCP or a WebSocket upgrade
 - client_store: Arc<Mutex<Store>> object that represents the key-value store
 
 Returns:
 - Result<()>: An empty Ok result is returned on success, or an Err result on failure.
 */
 
-async fn handle_connection(stream: TcpStream, client_store: Arc<Mutex<Store>>) -> Result<()> {
-  // Create a RespConnection object for the stream.
-let mut conn = resp::RespConnection::new(stream);
-
+async fn handle_connection(mut conn: RespConnection, client_store: Arc<Mutex<Store>>) -> Result<()> {
 // Loop continuously to handle incoming commands until the connection is closed.
 loop {
 
-    // Read the next value from the connection.
-    let value = conn.read_value().await?;
+    // Drain every command already pipelined into the buffer (issuing a socket
+    // read only if the buffer holds nothing but a partial frame).
+    let values = conn.read_values_ready().await?;
 
-    if let Some(value) = value {
+    if values.is_empty() {
+        // If there are no more values to proccess
+        break;
+    }
+
+    let mut responses = Vec::with_capacity(values.len());
 
-        // Convert the value to command and its arguments.
-        let (command, args) = value.to_command()?;
+    for value in values {
 
+        // Convert the value to command and its arguments. A malformed
+        // command becomes an error response rather than aborting the whole
+        // batch, so earlier (valid) responses in this pipelined read still
+        // get flushed back to the client.
+        let response = match value.to_command() {
+        Err(e) => Error(format!("ERR {}", e)),
+        Ok((command, args)) =>
         // Match the command with a handler function and return the response.
-        let response = match command.to_ascii_lowercase().as_ref() {
+        match command.to_ascii_lowercase().as_ref() {
+            // Handle the "hello" command by negotiating the protocol version, if one
+            // was requested, before replying with server info in that version.
+            "hello" => {
+                match args.first() {
+                    Some(BulkString(requested)) => match requested.parse::<u8>() {
+                        Ok(version) if SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => {
+                            conn.set_protocol_version(version);
+                            hello_reply(&conn)
+                        }
+                        _ => Error(format!(
+                            "NOPROTO unsupported protocol version: {}",
+                            requested
+                        )),
+                    },
+                    // No version requested: report the currently negotiated one.
+                    None => hello_reply(&conn),
+                    Some(_) => Error("HELLO's protocol argument must be a bulk string".to_string()),
+                }
+            },
+
             // Handle the "ping" command with a "PONG" response.
-            "PING" => SimpleString("PONG".to_string()),
+            "ping" => SimpleString("PONG".to_string()),
 
             // Handle the "echo" command with the first argument as the response.
-            "ECHO" => args.first().unwrap().clone(),
+            "echo" => args.first().unwrap().clone(),
 
             // Handle the "get" command by retrieving the value associated with the key from the store.
-            "GET" => {
-                if let Some(BulkString(key)) = args.get(0) {
+            "get" => {
+                if let Some(BulkString(key)) = args.first() {
                     if let Some(val) = client_store.lock().unwrap().get(key.clone()) {
                         SimpleString(val)
                     } else {
@@ -56,8 +204,8 @@ loop {
             },
 
             // Handle the "set" command by setting the value associated with the key in the store.
-            "SET" => {
-                if let (Some(BulkString(key)), Some(BulkString(value))) = (args.get(0), args.get(1)) {
+            "set" => {
+                if let (Some(BulkString(key)), Some(BulkString(value))) = (args.first(), args.get(1)) {
                     client_store.lock().unwrap().set(key.clone(), value.clone());
                     SimpleString("OK".to_string())
                 } else {
@@ -65,9 +213,28 @@ loop {
                 }
             },
 
+            // Handle the "setstream" command: like "set", but the value is
+            // sent separately as a streamed sequence of chunk frames (see
+            // `RespConnection::read_value_streaming`) rather than embedded in
+            // the command array, so a large value never has to be fully
+            // buffered before the command reaches the dispatcher.
+            "setstream" => {
+                if let Some(BulkString(key)) = args.first() {
+                    match collect_streamed_chunks(&mut conn).await {
+                        Ok(chunks) => match client_store.lock().unwrap().set_streaming(key.clone(), chunks) {
+                            Ok(()) => SimpleString("OK".to_string()),
+                            Err(e) => Error(format!("error assembling streamed value: {}", e)),
+                        },
+                        Err(e) => Error(format!("error reading streamed value: {}", e)),
+                    }
+                } else {
+                    Error("Setstream requires one argument".to_string())
+                }
+            },
+
             // Handle the "del" command by deleting the key and its associated value from the store.
-            "DEL" => {
-                if let Some(BulkString(key)) = args.get(0) {
+            "del" => {
+                if let Some(BulkString(key)) = args.first() {
                     if let Some(val) = client_store.lock().unwrap().get(key.clone()) {
                         client_store.lock().unwrap().del(key.clone());
                         SimpleString("DELETED".to_string())
@@ -80,8 +247,8 @@ loop {
             },
 
             // Handle the "exists" command by checking if the key exists in the store.
-            "EXISTS" => {
-                if let Some(BulkString(key)) = args.get(0) {
+            "exists" => {
+                if let Some(BulkString(key)) = args.first() {
                     if let Some(val) = client_store.lock().unwrap().get(key.clone()) {
                         SimpleString("1".to_string())
                     } else {
@@ -94,51 +261,119 @@ loop {
 
             // If the command is not implemented, return an error response.
             _ => Error(format!("command not implemented: {}", command)),
+        },
         };
 
-        // Write the response back to the connection.
-        conn.write_value(response).await?;
+        responses.push(response);
+    }
 
-    } else {
+    // Flush every response from this batch back to the connection in one write.
+    conn.write_values(responses).await?;
+  }
+  Ok(())
+}
 
-        // If there are no more values to proccess
-        break;
+// Listens for raw TCP connections on `addr`, spawning a task per connection
+// that speaks RESP directly over the socket.
+async fn run_tcp_listener(addr: &str, main_store: Arc<Mutex<Store>>) -> Result<()> {
+  let listener = TcpListener::bind(addr).await?;
+  println!("listening for TCP connections on {}", addr);
+
+  loop {
+    let incoming = listener.accept().await;
+    let client_store = main_store.clone();
+    match incoming {
+      Ok((stream, _)) => {
+        println!("accepted new TCP connection");
+        tokio::spawn(async move {
+          handle_connection(RespConnection::new(stream), client_store).await.unwrap();
+        });
+      }
+      Err(e) => {
+        println!("error: {}", e);
+      }
     }
   }
-  Ok(())
 }
 
-/*
+// Listens for WebSocket upgrade requests on `addr`, spawning a task per
+// connection that frames each RESP request/response as a binary WebSocket
+// message.
+async fn run_websocket_listener(addr: &str, main_store: Arc<Mutex<Store>>) -> Result<()> {
+  let listener = TcpListener::bind(addr).await?;
+  println!("listening for WebSocket connections on {}", addr);
 
-Description: This is the main function for a Rust key-value store. It listens for incoming TCP connections on "127.0.0.1:4200" and spawns a new async task to handle each connection. It uses a shared store represented by an Arc wrapped around a Mutex, to handle all incoming requests.
-Args: None
-Returns: A Result type indicating whether the function executed successfully or an error occurred.
-*/
-#[tokio::main]
-async fn main() -> Result<()> {
-  // Bind the TCP listener to "127.0.0.1:4200"
-  let listener = TcpListener::bind("127.0.0.1:4200").await?;
-  // Create a shared store using an Arc wrapped around a Mutex
-  let main_store = Arc::new(Mutex::new(Store::new()));
-  
-  // Enter an infinite loop to handle incoming connections
   loop {
-  // Accept incoming connections
     let incoming = listener.accept().await;
-    // Clone the shared store for each incoming connection
     let client_store = main_store.clone();
-    // Handle incoming connections in a separate async task
     match incoming {
-      
       Ok((stream, _)) => {
-      println!("accepted new connection");
-      tokio::spawn(async move {
-        handle_connection(stream, client_store).await.unwrap();
-      });
+        tokio::spawn(async move {
+          match tokio_tungstenite::accept_async(stream).await {
+            Ok(ws_stream) => {
+              println!("accepted new WebSocket connection");
+              handle_connection(RespConnection::new_websocket(ws_stream), client_store).await.unwrap();
+            }
+            Err(e) => {
+              println!("websocket handshake error: {}", e);
+            }
+          }
+        });
       }
       Err(e) => {
         println!("error: {}", e);
       }
     }
   }
+}
+
+// Runs the listener(s) selected by `mode` until one of them returns an error.
+async fn run_server(mode: TransportMode, main_store: Arc<Mutex<Store>>) -> Result<()> {
+  match mode {
+    TransportMode::Tcp => run_tcp_listener("127.0.0.1:4200", main_store).await,
+    TransportMode::WebSocket => run_websocket_listener("127.0.0.1:4201", main_store).await,
+    // try_join! (rather than join!) so a bind failure on either listener
+    // surfaces immediately instead of waiting on the other one, which only
+    // returns by erroring out itself and otherwise loops forever.
+    TransportMode::Both => {
+      tokio::try_join!(
+        run_tcp_listener("127.0.0.1:4200", main_store.clone()),
+        run_websocket_listener("127.0.0.1:4201", main_store.clone()),
+      )?;
+      Ok(())
+    }
+  }
+}
+
+/*
+
+Description: This is the main function for a Rust key-value store. By default it listens for raw TCP connections on "127.0.0.1:4200"; pass `--transport=ws` to instead listen for WebSocket upgrades on "127.0.0.1:4201", or `--transport=both` to run both listeners concurrently. Pass `--bench` (with optional `--count=` / `--payload-size=`) to instead run a SET/GET load benchmark against the real encode/parse path (each command encoded and parsed back on its own, not pipelined) and exit. It uses a shared store represented by an Arc wrapped around a Mutex, to handle all incoming requests.
+Args: None
+Returns: A Result type indicating whether the function executed successfully or an error occurred.
+*/
+#[tokio::main]
+async fn main() -> Result<()> {
+  if let Some((count, payload_size)) = parse_bench_args() {
+    bench::run(count, payload_size);
+    return Ok(());
+  }
+
+  // Create a shared store using an Arc wrapped around a Mutex
+  let main_store = Arc::new(Mutex::new(Store::new()));
+
+  #[cfg(feature = "profiling")]
+  let profiler_guard = start_profiler();
+
+  let result = tokio::select! {
+    result = run_server(parse_transport_mode(), main_store) => result,
+    _ = tokio::signal::ctrl_c() => {
+      println!("shutdown signal received, stopping");
+      Ok(())
+    }
+  };
+
+  #[cfg(feature = "profiling")]
+  write_profile(profiler_guard);
+
+  result
 }
\ No newline at end of file
@@ -1,6 +1,13 @@
+use std::pin::Pin;
+
 use anyhow::{Error, Result};
-use bytes::BytesMut;
+use async_stream::stream;
+use bytes::{Bytes, BytesMut};
+use futures::{SinkExt, Stream, StreamExt};
 use tokio::{io::AsyncReadExt, io::AsyncWriteExt, net::TcpStream};
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+use crate::bytes_buf::BytesBuf;
 
 // The ASCII value of the carriage return character.
 const CARRIAGE_RETURN: u8 = '\r' as u8;
@@ -8,6 +15,11 @@ const CARRIAGE_RETURN: u8 = '\r' as u8;
 // The ASCII value of the newline character.
 const NEWLINE: u8 = '\n' as u8;
 
+// The maximum size of a single chunk frame used by the streaming bulk
+// string transfer. Larger payloads are split across multiple frames so the
+// sender never has to hold the whole body in memory at once.
+const STREAM_CHUNK_SIZE: usize = 16 * 1024;
+
 // The different types of values that can be stored in the key-value store.
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub enum Value {
@@ -16,6 +28,9 @@ pub enum Value {
     Error(String), // An error message.
     BulkString(String), // A bulk string value.
     Array(Vec<Value>), // An array of values.
+    Integer(i64), // A signed 64-bit integer value.
+    Boolean(bool), // A boolean value. RESP3 only; encodes as an integer under RESP2.
+    Map(Vec<(Value, Value)>), // An ordered collection of key/value pairs. RESP3 only; encodes as a flat array under RESP2.
 }
 
 impl Value {
@@ -26,45 +41,139 @@ impl Value {
             Value::Array(items) => {
                 // If the value is an array, return the first element as the command
                 // and the rest of the elements as the arguments.
-                return Ok((
-                    items.first().unwrap().unwrap_bulk(),
-                    items.clone().into_iter().skip(1).collect(),
-                ));
+                match items.first() {
+                    Some(Value::BulkString(command)) => Ok((
+                        command.clone(),
+                        items.clone().into_iter().skip(1).collect(),
+                    )),
+                    Some(_) => Err(Error::msg("command name must be a bulk string")),
+                    None => Err(Error::msg("empty command array")),
+                }
             }
             _ => Err(Error::msg("not an array")), // Return an error if the value is not an array.
         }
     }
 
-    // Returns the underlying string value of a bulk string value.
-    fn unwrap_bulk(&self) -> String {
+    // Encodes the value into a Redis protocol-compliant string, gated on the
+    // negotiated protocol `version` (2 or 3). RESP3-only representations
+    // (booleans, maps, the `_` null) fall back to their RESP2 equivalent
+    // when `version` is 2.
+    pub fn encode(self, version: u8) -> String {
         match self {
-            Value::BulkString(str) => str.clone(), // Return the string value if the value is a bulk string.
-            _ => panic!("not a bulk string"), // Panic if the value is not a bulk string.
-        }
-    }
-
-    // Encodes the value into a Redis protocol-compliant string.
-    pub fn encode(self) -> String {
-        match &self {
-            Value::Null => "$-1\r\n".to_string(), // Null values are represented as "$-1\r\n".
+            // Under RESP2 there's no dedicated null type, so null uses the null bulk string.
+            Value::Null if version < 3 => "$-1\r\n".to_string(),
+            // RESP3 has a dedicated null type: "_\r\n".
+            Value::Null => "_\r\n".to_string(),
             Value::SimpleString(s) => format!("+{}\r\n", s.as_str()), // Simple string values are represented as "+<string>\r\n".
             Value::Error(msg) => format!("-{}\r\n", msg.as_str()), // Error messages are represented as "-<error message>\r\n".
             Value::BulkString(s) => format!("${}\r\n{}\r\n", s.chars().count(), s), // Bulk string values are represented as "$<length>\r\n<string>\r\n".
-            _ => panic!("value encode not implemented for: {:?}", self), // Panic if the value is not one of the supported types.
+            Value::Integer(n) => format!(":{}\r\n", n), // Integer values are represented as ":<n>\r\n".
+            Value::Array(items) => {
+                // Arrays are represented as "*<n>\r\n" followed by each encoded item.
+                let mut out = format!("*{}\r\n", items.len());
+                for item in items {
+                    out.push_str(&item.encode(version));
+                }
+                out
+            }
+            // Under RESP2 there's no boolean type, so booleans fall back to 0/1 integers.
+            Value::Boolean(b) if version < 3 => format!(":{}\r\n", if b { 1 } else { 0 }),
+            // RESP3 booleans are represented as "#t\r\n" or "#f\r\n".
+            Value::Boolean(b) => format!("#{}\r\n", if b { 't' } else { 'f' }),
+            // Under RESP2 there's no map type, so maps fall back to a flat array of
+            // alternating keys and values.
+            Value::Map(pairs) if version < 3 => {
+                let mut out = format!("*{}\r\n", pairs.len() * 2);
+                for (key, value) in pairs {
+                    out.push_str(&key.encode(version));
+                    out.push_str(&value.encode(version));
+                }
+                out
+            }
+            // RESP3 maps are represented as "%<n>\r\n" followed by each key/value pair.
+            Value::Map(pairs) => {
+                let mut out = format!("%{}\r\n", pairs.len());
+                for (key, value) in pairs {
+                    out.push_str(&key.encode(version));
+                    out.push_str(&value.encode(version));
+                }
+                out
+            }
+        }
+    }
+}
+
+
+// The underlying byte transport a RespConnection speaks over. Raw TCP reads
+// and writes bytes directly; a WebSocket client instead exchanges discrete
+// binary messages, so each transport knows how to turn itself into "give me
+// the next chunk of bytes" / "send these bytes" to feed the same parsing and
+// framing logic either way.
+enum Transport {
+    Tcp(TcpStream),
+    WebSocket(WebSocketStream<TcpStream>),
+}
+
+impl Transport {
+    // Reads the next chunk of bytes from the remote host. Returns `None`
+    // once the remote host has closed the connection.
+    async fn read_chunk(&mut self) -> Result<Option<Bytes>> {
+        match self {
+            Transport::Tcp(stream) => {
+                let mut buf = BytesMut::with_capacity(512);
+                let bytes_read = stream.read_buf(&mut buf).await?;
+                if bytes_read == 0 {
+                    Ok(None)
+                } else {
+                    Ok(Some(buf.freeze()))
+                }
+            }
+            Transport::WebSocket(ws) => loop {
+                match ws.next().await {
+                    Some(Ok(Message::Binary(data))) => return Ok(Some(Bytes::from(data))),
+                    Some(Ok(Message::Close(_))) | None => return Ok(None),
+                    // Ignore non-binary control frames (ping/pong/text) and keep waiting
+                    // for the next binary message.
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.into()),
+                }
+            },
+        }
+    }
+
+    // Writes a chunk of bytes to the remote host, flushing it so batched
+    // writes land in a single TCP segment instead of trickling out.
+    async fn write_chunk(&mut self, bytes: &[u8]) -> Result<()> {
+        match self {
+            Transport::Tcp(stream) => {
+                stream.write_all(bytes).await?;
+                stream.flush().await?;
+                Ok(())
+            }
+            Transport::WebSocket(ws) => {
+                ws.send(Message::Binary(bytes.to_vec())).await?;
+                Ok(())
+            }
         }
     }
 }
 
+// The default protocol version a connection speaks before it negotiates a
+// different one via `HELLO`.
+const DEFAULT_PROTOCOL_VERSION: u8 = 2;
 
-// Define a RespConnection struct that holds a TcpStream and a buffer of bytes. 
+// Define a RespConnection struct that holds a Transport and a BytesBuf of
+// not-yet-consumed bytes. Bytes only leave `buffer` once a full value has
+// parsed successfully, so a partial read never loses already-buffered data.
 pub struct RespConnection {
-    stream: TcpStream,
-    buffer: BytesMut,
+    stream: Transport,
+    buffer: BytesBuf,
+    protocol_version: u8,
 }
 
 // Implementation of RespConnection methods.
 impl RespConnection {
-    // Create a new instance of RespConnection.
+    // Create a new instance of RespConnection over a raw TCP socket.
     // Args:
     // * `stream`: A TcpStream that represents a connection to a remote host.
     // Returns:
@@ -72,79 +181,231 @@ impl RespConnection {
     pub fn new(stream: TcpStream) -> Self {
         // Initialize a new instance of the RespConnection struct and return it.
         return RespConnection {
-            stream,
-            buffer: BytesMut::with_capacity(512),
+            stream: Transport::Tcp(stream),
+            buffer: BytesBuf::new(),
+            protocol_version: DEFAULT_PROTOCOL_VERSION,
         };
     }
 
-    // Read a value from the remote host.
-    // Args: None.
+    // Create a new instance of RespConnection over an upgraded WebSocket
+    // connection, framing each RESP request/response as a binary message.
+    // Args:
+    // * `stream`: A WebSocketStream representing an upgraded connection to a remote host.
     // Returns:
-    // * `Result<Option<Value>>`: The result of the operation. Contains either Some(Value) or None.
-    pub async fn read_value(&mut self) -> Result<Option<Value>> {
-        // Loop until we get a value.
+    // * `Self`: The new instance of the RespConnection struct.
+    pub fn new_websocket(stream: WebSocketStream<TcpStream>) -> Self {
+        return RespConnection {
+            stream: Transport::WebSocket(stream),
+            buffer: BytesBuf::new(),
+            protocol_version: DEFAULT_PROTOCOL_VERSION,
+        };
+    }
+
+    // Returns the protocol version this connection currently speaks (2 by
+    // default, or whatever was last negotiated via `HELLO`).
+    pub fn protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
+
+    // Sets the protocol version this connection speaks. Only `2` and `3`
+    // are meaningful; callers are expected to have validated the version
+    // before calling this (see the `HELLO` command handling in `main`).
+    pub fn set_protocol_version(&mut self, version: u8) {
+        self.protocol_version = version;
+    }
+
+    // Drains every complete value currently sitting in the buffer, reading
+    // more bytes from the remote host only when the buffer holds nothing
+    // but a partial frame. This lets a client that pipelines several
+    // commands into one TCP segment have all of them parsed out in one go,
+    // rather than one socket read per command.
+    // Returns an empty vector once the remote host has closed the connection.
+    //
+    // Stops draining as soon as it sees a streamed-body command (see
+    // `is_streamed_body_command`): its body follows as raw chunk frames, not
+    // another RESP value, so whatever comes after it in the buffer must be
+    // left untouched for `read_value_streaming` to consume.
+    pub async fn read_values_ready(&mut self) -> Result<Vec<Value>> {
+        let mut values = Vec::new();
+
         loop {
-            // Read bytes from the remote host into the buffer.
-            let bytes_read = self.stream.read_buf(&mut self.buffer).await?;
+            // Drain whatever full values are already buffered.
+            while let Some((value, bytes_consumed)) = parse_message(&self.buffer, 0)? {
+                self.buffer.take_exact(bytes_consumed);
+                let starts_streamed_body = is_streamed_body_command(&value);
+                values.push(value);
+                if starts_streamed_body {
+                    return Ok(values);
+                }
+            }
 
-            // If we didn't read any bytes, return None.
-            if bytes_read == 0 {
-                return Ok(None);
+            if !values.is_empty() {
+                return Ok(values);
             }
 
-            // Try to parse the buffer for a value.
-            if let Some((value, _)) = parse_message(self.buffer.split())? {
-                // If we found a value, return it.
-                return Ok(Some(value));
+            // The buffer holds only a partial frame (or nothing at all): read more.
+            match self.stream.read_chunk().await? {
+                Some(chunk) => self.buffer.extend(chunk),
+                // Connection closed with nothing left to parse.
+                None => return Ok(values),
             }
         }
     }
 
-    // Write a value to the remote host.
+    // Writes every value in `values` in one batched write and a single
+    // flush, so a client that pipelined several commands gets all of the
+    // responses back in one TCP segment instead of one write per response.
+    pub async fn write_values(&mut self, values: Vec<Value>) -> Result<()> {
+        let mut encoded = String::new();
+        for value in values {
+            encoded.push_str(&value.encode(self.protocol_version));
+        }
+
+        self.stream.write_chunk(encoded.as_bytes()).await
+    }
+
+    // Streams a large bulk value to the remote host as a sequence of
+    // length-prefixed chunk frames (a `u32` big-endian length followed by
+    // that many bytes), so the full body never has to be buffered in
+    // memory. A single zero-length frame marks the end of the stream.
     // Args:
-    // * `value`: The value to write.
-    // Returns:
-    // * `Result<()>`: The result of the operation.
-    pub async fn write_value(&mut self, value: Value) -> Result<()> {
-        // Encode the value and write it to the remote host.
-        self.stream.write(value.encode().as_bytes()).await?;
+    // * `body`: A stream of byte chunks making up the value. Each chunk is
+    //   split further if it's larger than `STREAM_CHUNK_SIZE`.
+    pub async fn write_value_streaming<S>(&mut self, mut body: S) -> Result<()>
+    where
+        S: Stream<Item = Result<Bytes>> + Unpin,
+    {
+        while let Some(piece) = body.next().await {
+            for frame in piece?.chunks(STREAM_CHUNK_SIZE) {
+                self.write_frame(frame).await?;
+            }
+        }
+
+        // The terminating frame: exactly one, and it carries no bytes.
+        self.write_frame(&[]).await
+    }
+
+    // Reads a streamed bulk value as a sequence of chunk frames written by
+    // `write_value_streaming`, yielding each chunk as soon as it arrives
+    // instead of waiting for the whole body.
+    //
+    // Boxed and pinned rather than returned as `impl Stream` because
+    // `async-stream`'s generated stream is self-referential and so isn't
+    // `Unpin`; `Pin<Box<_>>` is `Unpin` regardless, so callers can just
+    // call `.next()` on it directly instead of having to pin it themselves.
+    pub fn read_value_streaming(&mut self) -> Pin<Box<dyn Stream<Item = Result<Bytes>> + Send + '_>> {
+        Box::pin(stream! {
+            loop {
+                let len = match self.read_exact_buffered(4).await {
+                    Ok(bytes) => u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+
+                if len == 0 {
+                    // The end-of-stream frame.
+                    return;
+                }
+
+                match self.read_exact_buffered(len).await {
+                    Ok(bytes) => yield Ok(bytes),
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+        })
+    }
 
-        // Return Ok if everything went well.
-        Ok(())
+    // Writes a single chunk frame: a `u32` big-endian length prefix
+    // followed by the frame's bytes.
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let mut framed = Vec::with_capacity(4 + frame.len());
+        framed.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        framed.extend_from_slice(frame);
+        self.stream.write_chunk(&framed).await
+    }
+
+    // Reads exactly `n` bytes, first draining whatever is already buffered
+    // before issuing further reads from the transport.
+    async fn read_exact_buffered(&mut self, n: usize) -> Result<Bytes> {
+        while self.buffer.len() < n {
+            match self.stream.read_chunk().await? {
+                Some(chunk) => self.buffer.extend(chunk),
+                None => return Err(Error::msg("connection closed mid-frame")),
+            }
+        }
+
+        Ok(self
+            .buffer
+            .take_exact(n)
+            .expect("checked buffer.len() >= n above"))
     }
 }
 
-// Parse a message from a buffer.
-// Args:
-// * `buffer`: The buffer to parse.
+// Reports whether `value` is a command whose body is sent separately as a
+// streamed sequence of chunk frames (see `RespConnection::read_value_streaming`)
+// rather than embedded in the command array itself, e.g. `SETSTREAM key`.
+fn is_streamed_body_command(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Array(items)
+            if matches!(items.first(), Some(Value::BulkString(cmd)) if cmd.eq_ignore_ascii_case("SETSTREAM"))
+    )
+}
+
+// Parse a message out of `buffer` starting at absolute offset `start`,
+// without consuming anything from `buffer`. The caller is responsible for
+// advancing the buffer by the returned length once it has decided the parse
+// is final.
 // Returns:
-// * `Result<Option<(Value, usize)>>`: The result of the operation. Contains either Some(Value) or None.
-fn parse_message(buffer: BytesMut) -> Result<Option<(Value, usize)>> {
-    // Match the first byte of the buffer.
-    match buffer[0] as char {
+// * `Result<Option<(Value, usize)>>`: the parsed value and the number of
+//   bytes it occupies starting at `start`, or `None` if `buffer` does not
+//   yet hold a complete value.
+pub(crate) fn parse_message(buffer: &BytesBuf, start: usize) -> Result<Option<(Value, usize)>> {
+    let Some(tag) = buffer.byte_at(start) else {
+        return Ok(None);
+    };
+
+    // Match the first byte of the value.
+    match tag as char {
         // If it's a `+`, decode a simple string.
-        '+' => decode_simple_string(buffer),
+        '+' => decode_simple_string(buffer, start),
         // If it's a `*`, decode an array.
-        '*' => decode_array(buffer),
+        '*' => decode_array(buffer, start),
         // If it's a `$`, decode a bulk string.
-        '$' => decode_bulk_string(buffer),
+        '$' => decode_bulk_string(buffer, start),
+        // If it's a `:`, decode an integer.
+        ':' => decode_integer(buffer, start),
         // If it's something else, return an error.
         _ => Err(Error::msg("unrecognised message type")),
     }
 }
 
-// Decode a simple string.
-// Args:
-// * `buffer`: The buffer to decode.
-// Returns:
-// * `Result<Option<(Value, usize)>>`: The result of the operation. Contains either Some(Value) or None.
-fn decode_simple_string(buffer: BytesMut) -> Result<Option<(Value, usize)>> {
+// Decode a simple string starting at `start`.
+fn decode_simple_string(buffer: &BytesBuf, start: usize) -> Result<Option<(Value, usize)>> {
     // Try to read until CRLF.
-    if let Some((line, len)) = read_until_crlf(&buffer[1..]) {
+    if let Some((line, len)) = find_crlf(buffer, start + 1) {
         // If we read something, parse the string.
-        let str = parse_string(line)?;
+        let str = parse_string(&line)?;
 
         // Return the string as a simple string value.
+        Ok(Some((Value::SimpleString(str), 1 + len)))
+    } else {
+        Ok(None)
+    }
+}
+
+// Decode an integer starting at `start`.
+fn decode_integer(buffer: &BytesBuf, start: usize) -> Result<Option<(Value, usize)>> {
+    if let Some((line, len)) = find_crlf(buffer, start + 1) {
+        let n = parse_integer(&line)?;
+
+        Ok(Some((Value::Integer(n), 1 + len)))
+    } else {
         Ok(None)
     }
 }
@@ -152,24 +413,23 @@ fn decode_simple_string(buffer: BytesMut) -> Result<Option<(Value, usize)>> {
 // Takes a buffer of bytes containing an array of values and decodes it into a `Value::Array`.
 // Returns `Ok(Some((Value::Array(items), bytes_consumed)))` if successful, where `items` is the
 // vector of values contained in the array and `bytes_consumed` is the number of bytes consumed from
-// the input buffer. If the input buffer does not contain a complete array, returns `Ok(None)`.
-fn decode_array(buffer: BytesMut) -> Result<Option<(Value, usize)>> {
-    // Read the length of the array and the number of bytes consumed from the input buffer.
-    let (array_length, mut bytes_consumed) =
-        if let Some((line, len)) = read_until_crlf(&buffer[1..]) {
-            // Parse the length of the array from the input buffer.
-            let array_length = parse_integer(line)?;
-
-            (array_length, len + 1)
-        } else {
-            // If the input buffer does not contain a complete array, return `Ok(None)`.
-            return Ok(None);
-        };
+// `start`. If the input buffer does not contain a complete array, returns `Ok(None)`.
+fn decode_array(buffer: &BytesBuf, start: usize) -> Result<Option<(Value, usize)>> {
+    // Read the length of the array and the number of bytes consumed from `start`.
+    let (array_length, mut bytes_consumed) = if let Some((line, len)) = find_crlf(buffer, start + 1) {
+        // Parse the length of the array from the line.
+        let array_length = parse_integer(&line)?;
+
+        (array_length, 1 + len)
+    } else {
+        // If the input buffer does not contain a complete array, return `Ok(None)`.
+        return Ok(None);
+    };
 
     // Decode each value in the array and add it to the `items` vector.
     let mut items: Vec<Value> = Vec::new();
     for _ in 0..array_length {
-        if let Some((v, len)) = parse_message(BytesMut::from(&buffer[bytes_consumed..]))? {
+        if let Some((v, len)) = parse_message(buffer, start + bytes_consumed)? {
             items.push(v);
             bytes_consumed += len
         } else {
@@ -179,72 +439,78 @@ fn decode_array(buffer: BytesMut) -> Result<Option<(Value, usize)>> {
     }
 
     // Return the vector of values contained in the array and the number of bytes consumed from
-    // the input buffer as a tuple wrapped in `Ok(Some())`.
+    // `start` as a tuple wrapped in `Ok(Some())`.
     return Ok(Some((Value::Array(items), bytes_consumed)));
 }
 
 // Takes a buffer of bytes containing a bulk string and decodes it into a `Value::BulkString`.
-// Returns `Ok(Some((Value::BulkString(parse_string(&buffer[bytes_consumed..end_of_bulk])?), end_of_bulk_line)))` 
-// if successful, where `parse_string(&buffer[bytes_consumed..end_of_bulk])?` is the string value
-// contained in the bulk string, and `end_of_bulk_line` is the index of the next byte after the
-// end of the bulk string in the input buffer. If the input buffer does not contain a complete
-// bulk string, returns `Ok(None)`.
-fn decode_bulk_string(buffer: BytesMut) -> Result<Option<(Value, usize)>> {
-    // Read the length of the bulk string and the number of bytes consumed from the input buffer.
-    let (bulk_length, bytes_consumed) = if let Some((line, len)) = read_until_crlf(&buffer[1..]) {
-        // Parse the length of the bulk string from the input buffer.
-        let bulk_length = parse_integer(line)?;
-
-        (bulk_length, len + 1)
+// Returns `Ok(Some((Value::BulkString(...), end_of_bulk_line)))` if successful, where
+// `end_of_bulk_line` is the number of bytes the bulk string occupies starting at `start`. If
+// the input buffer does not contain a complete bulk string, returns `Ok(None)`.
+fn decode_bulk_string(buffer: &BytesBuf, start: usize) -> Result<Option<(Value, usize)>> {
+    // Read the length of the bulk string and the number of bytes consumed from `start`.
+    let (bulk_length, bytes_consumed) = if let Some((line, len)) = find_crlf(buffer, start + 1) {
+        // Parse the length of the bulk string from the line.
+        let bulk_length = parse_integer(&line)?;
+
+        (bulk_length, 1 + len)
     } else {
         // If the input buffer does not contain a complete bulk string, return `Ok(None)`.
         return Ok(None);
     };
 
-    // Calculate the index of the last byte in the bulk string and the index of the next byte after
-    // the end of the bulk string in the input buffer.
-    let end_of_bulk = bytes_consumed + (bulk_length as usize);
+    // Calculate the length of the bulk string body and of the whole value including the
+    // trailing CRLF.
+    let bulk_length = bulk_length as usize;
+    let end_of_bulk = bytes_consumed + bulk_length;
     let end_of_bulk_line = end_of_bulk + 2;
 
-    return if end_of_bulk_line <= buffer.len() {
-        // If the input buffer contains a complete bulk
-        Ok(Some((
-            Value::BulkString(parse_string(&buffer[bytes_consumed..end_of_bulk])?),
-            end_of_bulk_line,
-        )))
+    return if start + end_of_bulk_line <= buffer.len() {
+        // If the input buffer contains a complete bulk string, copy out just its body.
+        let body = buffer
+            .peek_at(start + bytes_consumed, bulk_length)
+            .expect("already checked buffer holds end_of_bulk_line bytes");
+        Ok(Some((Value::BulkString(parse_string(&body)?), end_of_bulk_line)))
     } else {
         Ok(None)
     };
 }
 
 
-// Function: read_until_crlf
+// Function: find_crlf
 //
 // Description:
-// This function takes a slice of bytes 'buffer' and searches for the first occurrence of
-// a carriage return character followed by a newline character. If found, it returns a tuple
-// containing a slice of bytes from the start of the buffer to just before the CRLF and the
-// index of the byte immediately after the CRLF. If not found, it returns None.
+// Searches `buffer`, starting at absolute offset `start`, for the first
+// occurrence of a carriage return character followed by a newline
+// character. If found, it returns a tuple containing the bytes from `start`
+// up to just before the CRLF and the number of bytes from `start` up to and
+// including the CRLF. If not found, it returns None.
 //
 // Args:
-// - buffer: a slice of bytes to search for CRLF
+// - buffer: the buffer to search
+// - start: the absolute offset to start searching from
 //
 // Returns:
-// - Some tuple containing a slice of bytes and an index if CRLF found, None otherwise
+// - Some tuple containing the line and its length (including the CRLF) if found, None otherwise
 //
-// Example use:
-// let buffer = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
-// let (line, index) = read_until_crlf(buffer).unwrap();
-// assert_eq!(line, b"GET / HTTP/1.1");
-// assert_eq!(index, 18);
-
-fn read_until_crlf(buffer: &[u8]) -> Option<(&[u8], usize)> {
-  for i in 1..buffer.len() {
-  if buffer[i - 1] == CARRIAGE_RETURN && buffer[i] == NEWLINE {
-    return Some((&buffer[0..(i - 1)], i + 1));
-  }
-}
-  return None;
+// Note: each `buffer.byte_at(i)` call walks the chunk `VecDeque` from the
+// front, so this is O(chunks) per byte rather than O(1) (no cursor is
+// cached across calls). Left as-is since the lines this scans (array and
+// bulk string length headers) are always short, regardless of how many
+// chunks the rest of the buffer is fragmented into; a cursor would only
+// earn its keep if callers started searching for CRLFs across arbitrarily
+// large runs of buffered data.
+
+fn find_crlf(buffer: &BytesBuf, start: usize) -> Option<(bytes::Bytes, usize)> {
+    let mut i = start + 1;
+    while i < buffer.len() {
+        if buffer.byte_at(i - 1)? == CARRIAGE_RETURN && buffer.byte_at(i)? == NEWLINE {
+            let line = buffer.peek_at(start, i - 1 - start)?;
+            return Some((line, i + 1 - start));
+        }
+        i += 1;
+    }
+    None
 }
 
 // Function: parse_string
@@ -291,4 +557,134 @@ String::from_utf8(bytes.to_vec()).map_err(|_| Error::msg("Could not parse string
 fn parse_integer(bytes: &[u8]) -> Result<i64> {
 let str_integer = parse_string(bytes)?;
 (str_integer.parse::<i64>()).map_err(|_| Error::msg("Could not parse integer"))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Feeds a full RESP message into `parse_message` one byte at a time,
+    // asserting it only succeeds once the whole message has arrived and
+    // that no already-buffered bytes are lost along the way.
+    fn parse_one_byte_at_a_time(input: &[u8]) -> Value {
+        let mut buf = BytesBuf::new();
+        for &byte in input {
+            buf.extend(bytes::Bytes::copy_from_slice(&[byte]));
+            match parse_message(&buf, 0).unwrap() {
+                Some((value, consumed)) => {
+                    assert_eq!(buf.len(), input.len(), "should only succeed once the whole message has arrived");
+                    assert_eq!(consumed, buf.len(), "should consume the whole buffer");
+                    return value;
+                }
+                None => continue,
+            }
+        }
+        panic!("never produced a value");
+    }
+
+    #[test]
+    fn simple_string_one_byte_at_a_time() {
+        let value = parse_one_byte_at_a_time(b"+OK\r\n");
+        assert_eq!(value, Value::SimpleString("OK".to_string()));
+    }
+
+    #[test]
+    fn bulk_string_one_byte_at_a_time() {
+        let value = parse_one_byte_at_a_time(b"$5\r\nhello\r\n");
+        assert_eq!(value, Value::BulkString("hello".to_string()));
+    }
+
+    #[test]
+    fn array_one_byte_at_a_time() {
+        let value = parse_one_byte_at_a_time(b"*2\r\n$3\r\nfoo\r\n$3\r\nbar\r\n");
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                Value::BulkString("foo".to_string()),
+                Value::BulkString("bar".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn partial_read_retains_buffered_bytes() {
+        let mut buf = BytesBuf::new();
+        buf.extend(bytes::Bytes::from_static(b"$5\r\nhel"));
+        assert!(parse_message(&buf, 0).unwrap().is_none());
+        assert_eq!(buf.len(), 7, "partial parse must not drop buffered bytes");
+
+        buf.extend(bytes::Bytes::from_static(b"lo\r\n"));
+        let (value, consumed) = parse_message(&buf, 0).unwrap().unwrap();
+        assert_eq!(value, Value::BulkString("hello".to_string()));
+        assert_eq!(consumed, buf.len());
+    }
+
+    // Sets up a connected loopback TCP pair of `RespConnection`s so the
+    // streaming frame logic can be exercised end to end.
+    async fn loopback_pair() -> (RespConnection, RespConnection) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let accept = tokio::spawn(async move { listener.accept().await.unwrap().0 });
+        let client = TcpStream::connect(addr).await.unwrap();
+        let server = accept.await.unwrap();
+
+        (RespConnection::new(server), RespConnection::new(client))
+    }
+
+    #[tokio::test]
+    async fn write_value_streaming_splits_chunks_larger_than_stream_chunk_size() {
+        let (mut server, mut client) = loopback_pair().await;
+
+        let payload = Bytes::from(vec![b'x'; STREAM_CHUNK_SIZE * 2 + 7]);
+        let payload_for_write = payload.clone();
+        let writer = tokio::spawn(async move {
+            let body = futures::stream::iter(vec![Ok(payload_for_write)]);
+            server.write_value_streaming(body).await.unwrap();
+        });
+
+        let mut received = BytesMut::new();
+        {
+            let mut body = client.read_value_streaming();
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk.unwrap();
+                assert!(chunk.len() <= STREAM_CHUNK_SIZE, "each frame must be at most STREAM_CHUNK_SIZE bytes");
+                received.extend_from_slice(&chunk);
+            }
+        }
+        writer.await.unwrap();
+
+        assert_eq!(received.freeze(), payload, "split frames must reassemble into the original bytes");
+    }
+
+    #[tokio::test]
+    async fn read_value_streaming_stops_after_single_terminator_frame() {
+        let (mut server, mut client) = loopback_pair().await;
+
+        let writer = tokio::spawn(async move {
+            let body = futures::stream::iter(vec![
+                Ok(Bytes::from_static(b"hello, ")),
+                Ok(Bytes::from_static(b"world")),
+            ]);
+            server.write_value_streaming(body).await.unwrap();
+            // A second, ordinary value sent right after the streamed one:
+            // `read_value_streaming` must consume exactly one terminator
+            // frame and leave this untouched for normal parsing.
+            server.write_values(vec![Value::SimpleString("OK".to_string())]).await.unwrap();
+        });
+
+        let mut received = BytesMut::new();
+        {
+            let mut body = client.read_value_streaming();
+            while let Some(chunk) = body.next().await {
+                received.extend_from_slice(&chunk.unwrap());
+            }
+        }
+        assert_eq!(received.freeze(), Bytes::from_static(b"hello, world"));
+
+        let trailing = client.read_values_ready().await.unwrap();
+        assert_eq!(trailing, vec![Value::SimpleString("OK".to_string())]);
+
+        writer.await.unwrap();
+    }
+}
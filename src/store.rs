@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 
+use anyhow::Result;
+use bytes::{Bytes, BytesMut};
+
 // Store is a struct that represents a key-value store
 pub struct Store {
     data: HashMap<String, String>,
@@ -23,6 +26,28 @@ impl Store {
         self.data.insert(key, value);
     }
 
+    // Sets a key's value by assembling it from a sequence of chunks, as
+    // collected from a streamed bulk value transfer (see
+    // `RespConnection::read_value_streaming`). This takes an already-collected
+    // `Vec<Bytes>` rather than consuming a live stream itself: `Store` is
+    // guarded by a plain (non-async-aware) `Mutex`, so the caller must finish
+    // pulling chunks off the connection *before* taking the lock, never while
+    // holding it across an `.await`.
+    //
+    // # Arguments
+    //
+    // * `key` - A string that represents the key
+    // * `chunks` - The byte chunks making up the value, in order
+    pub fn set_streaming(&mut self, key: String, chunks: Vec<Bytes>) -> Result<()> {
+        let mut value = BytesMut::new();
+        for chunk in chunks {
+            value.extend_from_slice(&chunk);
+        }
+
+        self.data.insert(key, String::from_utf8(value.to_vec())?);
+        Ok(())
+    }
+
     // Retrieves the value associated with the given key
     //
     // # Arguments